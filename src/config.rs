@@ -0,0 +1,221 @@
+//! Proxy configuration, loaded from a single YAML file at startup.
+
+use std::{collections::BTreeMap, net::SocketAddr, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ProxyError;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HomeserverConfig {
+    /// Where the real homeserver lives; everything the proxy doesn't
+    /// intercept is forwarded here unchanged.
+    pub base_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SsoConfig {
+    pub authorize_url: String,
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Base URL the IdP should redirect back to, i.e. this proxy's public
+    /// address - kept separate from `homeserver.base_url` since the two
+    /// are rarely the same host.
+    pub redirect_base: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PolicyTranslation {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PolicyConfig {
+    /// Bumping this forces every session to re-accept the policy, even ones
+    /// that already completed `m.login.terms` against an older version.
+    pub version: String,
+    /// Language code (e.g. `en`) to the translated name/URL.
+    pub languages: BTreeMap<String, PolicyTranslation>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct TermsConfig {
+    /// Policy id (e.g. `privacy_policy`) to its current version and
+    /// translations.
+    #[serde(default)]
+    pub policies: BTreeMap<String, PolicyConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UiaConfig {
+    /// Flows offered for the pre-existing "protect an account action"
+    /// endpoints (change password, delete device, upload signing keys).
+    #[serde(default = "UiaConfig::default_login_stages")]
+    pub login_stages: Vec<Vec<String>>,
+    /// Flows offered for `/register`. Kept separate from `login_stages`
+    /// since registration commonly wants `m.login.dummy`/`m.login.terms`
+    /// where the login-style endpoints want credentials.
+    #[serde(default = "UiaConfig::default_register_stages")]
+    pub register_stages: Vec<Vec<String>>,
+}
+
+impl UiaConfig {
+    fn default_login_stages() -> Vec<Vec<String>> {
+        vec![vec!["m.login.password".to_owned()]]
+    }
+
+    fn default_register_stages() -> Vec<Vec<String>> {
+        vec![vec!["m.login.dummy".to_owned()]]
+    }
+}
+
+impl Default for UiaConfig {
+    fn default() -> Self {
+        Self {
+            login_stages: Self::default_login_stages(),
+            register_stages: Self::default_register_stages(),
+        }
+    }
+}
+
+/// Which [`crate::session::SessionStore`] backend to run behind.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum SessionStoreConfig {
+    /// Single-process map. Fine for a standalone deployment; sessions
+    /// don't survive a restart and aren't visible to other replicas.
+    Memory {
+        #[serde(default = "SessionStoreConfig::default_ttl_seconds")]
+        ttl_seconds: u64,
+    },
+    /// Shared across replicas, so a follow-up request carrying a session id
+    /// can land on any of them.
+    Redis {
+        url: String,
+        #[serde(default = "SessionStoreConfig::default_ttl_seconds")]
+        ttl_seconds: u64,
+    },
+}
+
+impl SessionStoreConfig {
+    fn default_ttl_seconds() -> u64 {
+        3600
+    }
+}
+
+impl Default for SessionStoreConfig {
+    fn default() -> Self {
+        Self::Memory { ttl_seconds: Self::default_ttl_seconds() }
+    }
+}
+
+/// How `m.login.token` JWTs are verified. Either works; pick whichever
+/// matches how the issuer mints tokens. In both cases the `jti` claim is
+/// consumed as a single-use id and `exp` is enforced by the JWT itself.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "verifier", rename_all = "lowercase")]
+pub enum TokenConfig {
+    /// Shared HMAC secret the issuer signs tokens with.
+    Hmac {
+        secret: String,
+        /// Required `iss` claim, if the issuer sets one.
+        #[serde(default)]
+        issuer: Option<String>,
+        /// Required `aud` claim, if the issuer sets one.
+        #[serde(default)]
+        audience: Option<String>,
+    },
+    /// JWKS endpoint the issuer publishes its current signing keys at, so
+    /// keys can rotate without a config change. The token's `kid` header
+    /// picks which published key verifies it.
+    Jwks {
+        jwks_url: String,
+        issuer: String,
+        audience: String,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminConfig {
+    /// Bearer token the operator-facing endpoints (e.g. the terms-acceptances
+    /// query) require in their `Authorization` header. Left unconfigured,
+    /// those endpoints are disabled entirely rather than left open.
+    pub shared_secret: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WellKnownServerConfig {
+    /// The `m.server` value, e.g. `matrix.example.com:8448`.
+    pub m_server: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WellKnownConfig {
+    /// Lets a deployment that already publishes `/.well-known/matrix/client`
+    /// elsewhere turn this off rather than fight over who's authoritative.
+    #[serde(default = "WellKnownConfig::default_enabled")]
+    pub enabled: bool,
+    /// Published as `m.homeserver.base_url` - this proxy's own public
+    /// address, so clients get routed through it for the endpoints it
+    /// intercepts.
+    pub client_base_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity_server: Option<String>,
+    /// `/.well-known/matrix/server` is only served if this is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<WellKnownServerConfig>,
+}
+
+impl WellKnownConfig {
+    fn default_enabled() -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Config {
+    pub listen_address: SocketAddr,
+    pub homeserver: HomeserverConfig,
+    #[serde(default)]
+    pub uia: UiaConfig,
+    pub sso: Option<SsoConfig>,
+    pub token: Option<TokenConfig>,
+    #[serde(default)]
+    pub terms: TermsConfig,
+    #[serde(default)]
+    pub session_store: SessionStoreConfig,
+    pub well_known: Option<WellKnownConfig>,
+    pub admin: Option<AdminConfig>,
+}
+
+impl Config {
+    pub fn from_yaml_str(raw: &str) -> Result<Self, ProxyError> {
+        serde_yaml::from_str(raw).map_err(|err| ProxyError::Config(err.to_string()))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ProxyError> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|err| ProxyError::Config(err.to_string()))?;
+        Self::from_yaml_str(&raw)
+    }
+
+    /// The configured flows for the login-style (protect an existing
+    /// account) endpoints, translated from the configured stage names into
+    /// [`crate::uiaa::Flow`]s. [`crate::state::AppState::login_flows`] adds
+    /// the `m.login.sso`/`m.login.token` alternatives on top of these.
+    pub fn login_flows(&self) -> Vec<crate::uiaa::Flow> {
+        self.uia.login_stages.iter().map(|stages| crate::uiaa::Flow::new(stages.clone())).collect()
+    }
+
+    /// All flows a client may use to complete `/register`.
+    pub fn register_flows(&self) -> Vec<crate::uiaa::Flow> {
+        self.uia.register_stages.iter().map(|stages| crate::uiaa::Flow::new(stages.clone())).collect()
+    }
+}
+
+/// Values threaded into [`crate::uiaa::UiaaInfo::params`] for stages that
+/// need to advertise something to the client up front (IdP details,
+/// policy documents, ...). Keyed by stage type.
+pub type StageParams = BTreeMap<String, serde_json::Value>;
@@ -0,0 +1,53 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// Errors the proxy can surface to a client.
+///
+/// Each variant maps to a Matrix `errcode`/HTTP status pair so handlers can
+/// just `?` their way through fallible steps instead of building the JSON
+/// body by hand.
+#[derive(Debug, thiserror::Error)]
+pub enum ProxyError {
+    #[error("unknown or expired UIA session")]
+    UnknownSession,
+
+    #[error("stage {0} is not part of the requested flow")]
+    UnknownStage(String),
+
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("upstream homeserver request failed")]
+    Upstream(#[from] reqwest::Error),
+
+    #[error("session store error: {0}")]
+    SessionStore(String),
+
+    #[error("invalid configuration: {0}")]
+    Config(String),
+}
+
+impl ProxyError {
+    fn status_and_errcode(&self) -> (StatusCode, &'static str) {
+        match self {
+            ProxyError::UnknownSession => (StatusCode::FORBIDDEN, "M_UNKNOWN"),
+            ProxyError::UnknownStage(_) => (StatusCode::BAD_REQUEST, "M_BAD_JSON"),
+            ProxyError::Forbidden(_) => (StatusCode::FORBIDDEN, "M_FORBIDDEN"),
+            ProxyError::Upstream(_) => (StatusCode::BAD_GATEWAY, "M_UNKNOWN"),
+            ProxyError::SessionStore(_) => (StatusCode::INTERNAL_SERVER_ERROR, "M_UNKNOWN"),
+            ProxyError::Config(_) => (StatusCode::INTERNAL_SERVER_ERROR, "M_UNKNOWN"),
+        }
+    }
+}
+
+impl IntoResponse for ProxyError {
+    fn into_response(self) -> Response {
+        let (status, errcode) = self.status_and_errcode();
+        let body = json!({ "errcode": errcode, "error": self.to_string() });
+        (status, Json(body)).into_response()
+    }
+}
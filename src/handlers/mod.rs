@@ -0,0 +1,6 @@
+pub mod protected;
+pub mod register;
+pub mod sso;
+pub mod terms;
+pub mod uia;
+pub mod well_known;
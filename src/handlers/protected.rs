@@ -0,0 +1,53 @@
+//! Generic handler for the "protect an existing account action" endpoints
+//! (change password, delete device, upload cross-signing keys, ...): run
+//! whatever UIA flow is configured for them and only forward the request
+//! upstream once it's satisfied.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{OriginalUri, State},
+    http::{HeaderMap, Method},
+    response::Response,
+};
+use serde_json::json;
+
+use crate::{
+    error::ProxyError,
+    handlers::uia::{self, Outcome},
+    proxy,
+    state::AppState,
+};
+
+pub async fn protected(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ProxyError> {
+    let body_json = if body.is_empty() {
+        json!({})
+    } else {
+        serde_json::from_slice(&body).unwrap_or(json!({}))
+    };
+
+    match uia::process(&state, state.login_flows(), body_json).await? {
+        Outcome::Challenge(response) => Ok(response),
+        Outcome::Forward(forwarded) => {
+            let forwarded_body = Bytes::from(serde_json::to_vec(&forwarded).unwrap_or_default());
+            proxy::forward(
+                &state.http_client,
+                &state.config.homeserver.base_url,
+                method,
+                &uri.path_and_query()
+                    .map(|pq| pq.as_str().to_owned())
+                    .unwrap_or_else(|| uri.path().to_owned()),
+                headers,
+                forwarded_body,
+            )
+            .await
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! `POST /_matrix/client/r0/register`, driven through the configured
+//! registration UIA flow instead of being forwarded straight to the
+//! homeserver.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{OriginalUri, State},
+    http::HeaderMap,
+    response::Response,
+};
+use serde_json::json;
+
+use crate::{
+    error::ProxyError,
+    handlers::uia::{self, Outcome},
+    proxy,
+    state::AppState,
+};
+
+pub async fn register(
+    State(state): State<Arc<AppState>>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ProxyError> {
+    let body_json = if body.is_empty() {
+        json!({})
+    } else {
+        serde_json::from_slice(&body).unwrap_or(json!({}))
+    };
+
+    match uia::process(&state, state.config.register_flows(), body_json).await? {
+        Outcome::Challenge(response) => Ok(response),
+        Outcome::Forward(forwarded) => {
+            let forwarded_body = Bytes::from(serde_json::to_vec(&forwarded).unwrap_or_default());
+            proxy::forward(
+                &state.http_client,
+                &state.config.homeserver.base_url,
+                axum::http::Method::POST,
+                &uri.path_and_query()
+                    .map(|pq| pq.as_str().to_owned())
+                    .unwrap_or_else(|| uri.path().to_owned()),
+                headers,
+                forwarded_body,
+            )
+            .await
+        }
+    }
+}
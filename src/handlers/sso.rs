@@ -0,0 +1,71 @@
+//! The browser-facing half of the `m.login.sso` stage: a redirect to the
+//! configured IdP, and the callback it sends the user's browser back to.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::{error::ProxyError, session, stages::sso::SsoStage, state::AppState};
+
+#[derive(Deserialize)]
+pub struct FallbackQuery {
+    session: String,
+}
+
+pub async fn fallback(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FallbackQuery>,
+) -> Result<Response, ProxyError> {
+    let sso_stage = state
+        .sso_stage
+        .as_ref()
+        .ok_or_else(|| ProxyError::Config("m.login.sso is not configured".to_owned()))?;
+
+    // The session must already exist - it's created by the 401 challenge on
+    // the endpoint this fallback is completing a stage for.
+    state.session_store.get(&query.session).await?.ok_or(ProxyError::UnknownSession)?;
+
+    let state_token = session::random_id();
+    state.session_store.put_sso_state(&state_token, &query.session).await?;
+
+    let authorize_url = sso_stage.authorize_url(&state_token)?;
+
+    Ok((StatusCode::FOUND, [(header::LOCATION, authorize_url.to_string())]).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    state: String,
+    code: String,
+}
+
+pub async fn callback(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Response, ProxyError> {
+    let sso_stage = state
+        .sso_stage
+        .as_ref()
+        .ok_or_else(|| ProxyError::Config("m.login.sso is not configured".to_owned()))?;
+
+    let session_id = state
+        .session_store
+        .take_sso_state(&query.state)
+        .await?
+        .ok_or_else(|| ProxyError::Forbidden("unknown or already-used state".to_owned()))?;
+
+    let mut session =
+        state.session_store.get(&session_id).await?.ok_or(ProxyError::UnknownSession)?;
+
+    sso_stage.exchange_code(&state.http_client, &query.code).await?;
+
+    SsoStage::mark_verified(&mut session);
+    state.session_store.save(&session).await?;
+
+    Ok((StatusCode::OK, "SSO authentication complete, you may return to the app.").into_response())
+}
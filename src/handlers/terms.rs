@@ -0,0 +1,122 @@
+//! Query endpoint for durably logged `m.login.terms` acceptances, so an
+//! operator (or another internal service) can ask "who accepted policy X
+//! at version Y" without reaching into the session store directly.
+//!
+//! The acceptances are keyed by UIA session id and can reveal which
+//! sessions accepted which policy versions when, so this is gated behind
+//! the same admin bearer token as any other operator-only endpoint rather
+//! than left open to anyone who can reach the proxy.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::{error::ProxyError, state::AppState};
+
+fn authorize(state: &AppState, headers: &HeaderMap) -> Result<(), ProxyError> {
+    let Some(admin) = state.config.admin.as_ref() else {
+        return Err(ProxyError::Forbidden("this endpoint is not enabled".to_owned()));
+    };
+
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if presented != Some(admin.shared_secret.as_str()) {
+        return Err(ProxyError::Forbidden("missing or invalid admin credentials".to_owned()));
+    }
+
+    Ok(())
+}
+
+pub async fn acceptances(
+    State(state): State<Arc<AppState>>,
+    Path(subject): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ProxyError> {
+    // Not configured at all is reported the same way an unknown route would
+    // be, so its existence isn't leaked to an unauthenticated caller.
+    if state.config.admin.is_none() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    authorize(&state, &headers)?;
+
+    let acceptances = state.session_store.terms_acceptances(&subject).await?;
+    Ok(Json(acceptances).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::{HeaderMap, HeaderValue};
+
+    use super::*;
+    use crate::{
+        config::{AdminConfig, Config, HomeserverConfig},
+        session::InMemorySessionStore,
+    };
+
+    fn config(admin: Option<AdminConfig>) -> Config {
+        Config {
+            listen_address: "127.0.0.1:0".parse().unwrap(),
+            homeserver: HomeserverConfig { base_url: "https://matrix.example.com".to_owned() },
+            uia: Default::default(),
+            sso: None,
+            token: None,
+            terms: Default::default(),
+            session_store: Default::default(),
+            well_known: None,
+            admin,
+        }
+    }
+
+    fn state(admin: Option<AdminConfig>) -> Arc<AppState> {
+        Arc::new(AppState::new(config(admin), Arc::new(InMemorySessionStore::new(3600))))
+    }
+
+    #[tokio::test]
+    async fn not_found_when_admin_is_not_configured() {
+        let response = acceptances(State(state(None)), Path("subject".to_owned()), HeaderMap::new())
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn rejected_without_the_admin_bearer_token() {
+        let admin = Some(AdminConfig { shared_secret: "s3cret".to_owned() });
+        let err = acceptances(State(state(admin)), Path("subject".to_owned()), HeaderMap::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProxyError::Forbidden(_)));
+    }
+
+    #[tokio::test]
+    async fn rejected_with_the_wrong_admin_bearer_token() {
+        let admin = Some(AdminConfig { shared_secret: "s3cret".to_owned() });
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer wrong"));
+
+        let err = acceptances(State(state(admin)), Path("subject".to_owned()), headers).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Forbidden(_)));
+    }
+
+    #[tokio::test]
+    async fn accepted_with_the_correct_admin_bearer_token() {
+        let admin = Some(AdminConfig { shared_secret: "s3cret".to_owned() });
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer s3cret"));
+
+        let response =
+            acceptances(State(state(admin)), Path("subject".to_owned()), headers).await.unwrap();
+        assert_eq!(response.into_response().status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,155 @@
+//! Shared UIA bookkeeping used by every endpoint the proxy guards: look at
+//! the request body's `auth` field, either issue a fresh challenge or run
+//! the named stage, and tell the caller whether it's now safe to forward
+//! the request upstream.
+
+use axum::{http::StatusCode, response::IntoResponse, response::Response, Json};
+use serde_json::Value;
+
+use crate::{error::ProxyError, session::UiaSession, state::AppState, uiaa::{Flow, UiaaInfo}};
+
+#[derive(Debug)]
+pub enum Outcome {
+    /// The flow isn't satisfied yet; send this 401 back to the client.
+    Challenge(Response),
+    /// Every stage of some flow is done; forward the request body (with
+    /// `auth` stripped out) upstream.
+    Forward(Value),
+}
+
+pub async fn process(state: &AppState, flows: Vec<Flow>, body_json: Value) -> Result<Outcome, ProxyError> {
+    let auth = body_json.get("auth").cloned();
+
+    let Some(auth) = auth else {
+        let session = state.session_store.create(flows, state.stage_params()).await?;
+        return Ok(Outcome::Challenge(challenge_response(&session_to_uiaa(
+            &session,
+            Some("Additional authentication information required"),
+        ))));
+    };
+
+    let session_id = auth
+        .get("session")
+        .and_then(Value::as_str)
+        .ok_or_else(|| ProxyError::Forbidden("missing auth.session".to_owned()))?;
+    let stage_type = auth
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or_else(|| ProxyError::Forbidden("missing auth.type".to_owned()))?;
+
+    let Some(mut session) = state.session_store.get(session_id).await? else {
+        // An unknown or expired session is indistinguishable, from the
+        // client's point of view, from one that was never issued - hand
+        // back a fresh challenge instead of a hard error, the same as a
+        // request with no `auth` at all.
+        let session = state.session_store.create(flows, state.stage_params()).await?;
+        return Ok(Outcome::Challenge(challenge_response(&session_to_uiaa(
+            &session,
+            Some("Additional authentication information required"),
+        ))));
+    };
+    if !session.flows.iter().any(|flow| flow.stages.iter().any(|s| s == stage_type)) {
+        return Err(ProxyError::UnknownStage(stage_type.to_owned()));
+    }
+    let stage =
+        state.stages.get(stage_type).ok_or_else(|| ProxyError::UnknownStage(stage_type.to_owned()))?;
+
+    stage.submit(&mut session, &auth, state.session_store.as_ref()).await?;
+    // A terms version bump since this session was challenged revokes an
+    // earlier m.login.terms completion, so it has to be accepted again.
+    if let Some(served) = session.params.get("m.login.terms").and_then(|params| params.get("policies")) {
+        let terms_outdated = state.config.terms.policies.iter().any(|(id, policy)| {
+            served.get(id).and_then(|p| p.get("version")).and_then(Value::as_str)
+                != Some(policy.version.as_str())
+        });
+        if terms_outdated {
+            session.completed.retain(|s| s != "m.login.terms");
+        }
+    }
+    state.session_store.save(&session).await?;
+
+    if !session.is_complete() {
+        return Ok(Outcome::Challenge(challenge_response(&session_to_uiaa(&session, None))));
+    }
+
+    let mut forwarded = body_json;
+    if let Value::Object(ref mut map) = forwarded {
+        map.remove("auth");
+    }
+    Ok(Outcome::Forward(forwarded))
+}
+
+fn session_to_uiaa(session: &UiaSession, error: Option<&str>) -> UiaaInfo {
+    UiaaInfo {
+        flows: session.flows.clone(),
+        params: session.params.clone(),
+        session: session.id.clone(),
+        completed: session.completed.clone(),
+        error: error.map(ToOwned::to_owned),
+        errcode: error.map(|_| "M_FORBIDDEN".to_owned()),
+    }
+}
+
+fn challenge_response(info: &UiaaInfo) -> Response {
+    (StatusCode::UNAUTHORIZED, Json(info)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::{Config, HomeserverConfig},
+        session::InMemorySessionStore,
+    };
+
+    fn state() -> AppState {
+        let config = Config {
+            listen_address: "127.0.0.1:0".parse().unwrap(),
+            homeserver: HomeserverConfig { base_url: "https://matrix.example.com".to_owned() },
+            uia: Default::default(),
+            sso: None,
+            token: None,
+            terms: Default::default(),
+            session_store: Default::default(),
+            well_known: None,
+            admin: None,
+        };
+        AppState::new(config, Arc::new(InMemorySessionStore::new(3600)))
+    }
+
+    #[tokio::test]
+    async fn a_stage_not_offered_by_the_session_is_rejected_even_if_globally_registered() {
+        let state = state();
+        // m.login.dummy is globally registered (it's the default /register
+        // flow), but this particular session only ever offered
+        // m.login.password - submitting the other stage must not run it.
+        let session = state
+            .session_store
+            .create(vec![Flow::new(["m.login.password"])], Default::default())
+            .await
+            .unwrap();
+
+        let body = json!({ "auth": { "type": "m.login.dummy", "session": session.id } });
+
+        let err = process(&state, vec![Flow::new(["m.login.password"])], body).await.unwrap_err();
+        assert!(matches!(err, ProxyError::UnknownStage(_)));
+    }
+
+    #[tokio::test]
+    async fn an_unknown_session_id_gets_a_fresh_challenge_instead_of_a_hard_error() {
+        let state = state();
+        let body = json!({
+            "auth": { "type": "m.login.dummy", "session": "session-that-was-never-issued" }
+        });
+
+        let outcome = process(&state, vec![Flow::new(["m.login.dummy"])], body).await.unwrap();
+        let Outcome::Challenge(response) = outcome else {
+            panic!("expected a fresh challenge, not a forwarded request");
+        };
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
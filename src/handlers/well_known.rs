@@ -0,0 +1,132 @@
+//! `/.well-known/matrix/client` (and optionally `/server`), so a client
+//! that only knows a domain gets routed through this proxy for the
+//! endpoints it intercepts while still reaching the real homeserver for
+//! everything else.
+
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, response::Response, Json};
+use serde_json::json;
+
+use crate::state::AppState;
+
+pub async fn client(State(state): State<Arc<AppState>>) -> Response {
+    let Some(well_known) = state.config.well_known.as_ref().filter(|w| w.enabled) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let mut body = json!({ "m.homeserver": { "base_url": well_known.client_base_url } });
+    if let Some(identity_server) = &well_known.identity_server {
+        body["m.identity_server"] = json!({ "base_url": identity_server });
+    }
+
+    Json(body).into_response()
+}
+
+pub async fn server(State(state): State<Arc<AppState>>) -> Response {
+    let enabled_server = state
+        .config
+        .well_known
+        .as_ref()
+        .filter(|w| w.enabled)
+        .and_then(|w| w.server.as_ref());
+
+    let Some(server) = enabled_server else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    Json(json!({ "m.server": server.m_server })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::{
+        config::{Config, HomeserverConfig, WellKnownConfig, WellKnownServerConfig},
+        session::InMemorySessionStore,
+    };
+
+    fn config(well_known: Option<WellKnownConfig>) -> Config {
+        Config {
+            listen_address: "127.0.0.1:0".parse().unwrap(),
+            homeserver: HomeserverConfig { base_url: "https://matrix.example.com".to_owned() },
+            uia: Default::default(),
+            sso: None,
+            token: None,
+            terms: Default::default(),
+            session_store: Default::default(),
+            well_known,
+            admin: None,
+        }
+    }
+
+    fn state(well_known: Option<WellKnownConfig>) -> Arc<AppState> {
+        Arc::new(AppState::new(config(well_known), Arc::new(InMemorySessionStore::new(3600))))
+    }
+
+    #[tokio::test]
+    async fn client_is_not_found_when_well_known_is_not_configured() {
+        let response = client(State(state(None))).await;
+        assert_eq!(response.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn client_is_not_found_when_explicitly_disabled() {
+        let well_known = WellKnownConfig {
+            enabled: false,
+            client_base_url: "https://proxy.example.com".to_owned(),
+            identity_server: None,
+            server: None,
+        };
+        let response = client(State(state(Some(well_known)))).await;
+        assert_eq!(response.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn client_publishes_the_identity_server_when_configured() {
+        let well_known = WellKnownConfig {
+            enabled: true,
+            client_base_url: "https://proxy.example.com".to_owned(),
+            identity_server: Some("https://id.example.com".to_owned()),
+            server: None,
+        };
+        let response = client(State(state(Some(well_known)))).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["m.identity_server"]["base_url"], "https://id.example.com");
+    }
+
+    #[tokio::test]
+    async fn server_is_not_found_unless_explicitly_configured() {
+        let well_known = WellKnownConfig {
+            enabled: true,
+            client_base_url: "https://proxy.example.com".to_owned(),
+            identity_server: None,
+            server: None,
+        };
+        let response = server(State(state(Some(well_known)))).await;
+        assert_eq!(response.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn server_is_published_once_configured() {
+        let well_known = WellKnownConfig {
+            enabled: true,
+            client_base_url: "https://proxy.example.com".to_owned(),
+            identity_server: None,
+            server: Some(WellKnownServerConfig { m_server: "matrix.example.com:8448".to_owned() }),
+        };
+        let response = server(State(state(Some(well_known)))).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["m.server"], "matrix.example.com:8448");
+    }
+}
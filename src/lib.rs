@@ -0,0 +1,9 @@
+pub mod config;
+pub mod error;
+pub mod handlers;
+pub mod proxy;
+pub mod server;
+pub mod session;
+pub mod stages;
+pub mod state;
+pub mod uiaa;
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use uia_proxy::{
+    config::{Config, SessionStoreConfig},
+    server,
+    session::{redis_store::RedisSessionStore, InMemorySessionStore, SessionStore},
+    state::AppState,
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let config_path =
+        std::env::var("UIA_PROXY_CONFIG").unwrap_or_else(|_| "config.yaml".to_owned());
+    let config = Config::from_file(&config_path)?;
+    let listen_address = config.listen_address;
+
+    let session_store: Arc<dyn SessionStore> = match &config.session_store {
+        SessionStoreConfig::Memory { ttl_seconds } => Arc::new(InMemorySessionStore::new(*ttl_seconds)),
+        SessionStoreConfig::Redis { url, ttl_seconds } => {
+            Arc::new(RedisSessionStore::new(url, *ttl_seconds)?)
+        }
+    };
+    let state = Arc::new(AppState::new(config, session_store));
+    let app = server::build_router(state);
+
+    tracing::info!(%listen_address, "starting uia-proxy");
+    let listener = tokio::net::TcpListener::bind(listen_address).await?;
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
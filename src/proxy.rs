@@ -0,0 +1,40 @@
+//! Transparent forwarding to the real homeserver for anything the proxy
+//! doesn't need to intercept (and for intercepted requests once their UIA
+//! flow is satisfied).
+
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use crate::error::ProxyError;
+
+pub async fn forward(
+    client: &reqwest::Client,
+    homeserver_base_url: &str,
+    method: Method,
+    path_and_query: &str,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ProxyError> {
+    let url = format!("{}{}", homeserver_base_url.trim_end_matches('/'), path_and_query);
+    let method =
+        reqwest::Method::from_bytes(method.as_str().as_bytes()).unwrap_or(reqwest::Method::GET);
+
+    let mut upstream = client.request(method, url);
+    for (name, value) in headers.iter() {
+        // Let reqwest/hyper set host + content-length for the new request.
+        if name == axum::http::header::HOST || name == axum::http::header::CONTENT_LENGTH {
+            continue;
+        }
+        upstream = upstream.header(name, value);
+    }
+
+    let res = upstream.body(body).send().await?;
+
+    let status = StatusCode::from_u16(res.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let body = res.bytes().await?;
+
+    Ok((status, body).into_response())
+}
@@ -0,0 +1,54 @@
+//! Route table: the small set of endpoints the proxy intercepts, plus a
+//! catch-all that forwards everything else to the real homeserver.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method},
+    response::Response,
+    routing::{delete, get, post},
+    Router,
+};
+
+use crate::{error::ProxyError, handlers, proxy, state::AppState};
+
+pub fn build_router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/.well-known/matrix/client", get(handlers::well_known::client))
+        .route("/.well-known/matrix/server", get(handlers::well_known::server))
+        .route(
+            "/_matrix/client/r0/auth/m.login.sso/fallback/web",
+            get(handlers::sso::fallback),
+        )
+        .route("/_matrix/client/r0/auth/m.login.sso/callback", get(handlers::sso::callback))
+        .route("/_matrix/client/r0/register", post(handlers::register::register))
+        .route("/_uia-proxy/terms-acceptances/:subject", get(handlers::terms::acceptances))
+        .route("/_matrix/client/r0/account/password", post(handlers::protected::protected))
+        .route("/_matrix/client/r0/devices/:device_id", delete(handlers::protected::protected))
+        .route(
+            "/_matrix/client/r0/keys/device_signing/upload",
+            post(handlers::protected::protected),
+        )
+        .fallback(catch_all)
+        .with_state(state)
+}
+
+async fn catch_all(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    axum::extract::OriginalUri(uri): axum::extract::OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ProxyError> {
+    proxy::forward(
+        &state.http_client,
+        &state.config.homeserver.base_url,
+        method,
+        &uri.path_and_query().map(|pq| pq.as_str().to_owned()).unwrap_or_else(|| uri.path().to_owned()),
+        headers,
+        body,
+    )
+    .await
+}
@@ -0,0 +1,331 @@
+//! UIA session state and the [`SessionStore`] abstraction used to persist it.
+//!
+//! A session is created the first time a client hits a UIA-guarded endpoint
+//! without `auth`, and lives until every stage of one of its offered flows
+//! is completed. Per-session progress - the offered flows, completed
+//! stages, and stage scratch state such as the accepted terms versions -
+//! is kept on the session record itself. SSO `state` values, spent token
+//! ids and terms acceptances are separate records in the store, since they
+//! are looked up by something other than the session id or outlive it.
+
+pub mod redis_store;
+
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::{error::ProxyError, uiaa::Flow};
+
+/// Length of generated session and state ids: 32 alphanumeric characters,
+/// roughly 190 bits of randomness.
+const ID_LENGTH: usize = 32;
+
+pub fn random_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(ID_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("system clock before epoch").as_secs()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiaSession {
+    pub id: String,
+    pub flows: Vec<Flow>,
+    #[serde(default)]
+    pub params: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub completed: Vec<String>,
+    /// Stage-specific scratch state, e.g. the accepted terms versions or the
+    /// marker that the SSO callback verified this session.
+    #[serde(default)]
+    pub state: BTreeMap<String, Value>,
+    pub created_at: u64,
+}
+
+impl UiaSession {
+    pub fn new(flows: Vec<Flow>, params: BTreeMap<String, Value>) -> Self {
+        Self { id: random_id(), flows, params, completed: Vec::new(), state: BTreeMap::new(), created_at: now() }
+    }
+
+    pub fn complete_stage(&mut self, stage: &str) {
+        if !self.completed.iter().any(|s| s == stage) {
+            self.completed.push(stage.to_owned());
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.flows.iter().any(|flow| flow.stages.iter().all(|s| self.completed.contains(s)))
+    }
+}
+
+/// One `m.login.terms` acceptance, durably logged so a deployment can later
+/// answer "who accepted policy X at version Y" long after the UIA session
+/// that recorded it is gone.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TermsAcceptance {
+    pub policy_id: String,
+    pub version: String,
+    pub accepted_at: u64,
+}
+
+/// Pluggable backend for UIA session state.
+///
+/// Implementations differ in whether they can be shared across replicas
+/// behind a load balancer: the in-memory map only works for a single
+/// process, while the Redis-backed store lets any replica serve the
+/// follow-up request that carries a session id issued by another one.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn create(&self, flows: Vec<Flow>, params: BTreeMap<String, Value>) -> Result<UiaSession, ProxyError>;
+
+    async fn get(&self, session_id: &str) -> Result<Option<UiaSession>, ProxyError>;
+
+    async fn save(&self, session: &UiaSession) -> Result<(), ProxyError>;
+
+    /// Associate a freshly generated SSO `state` value with a session, so
+    /// the callback can look the session back up without trusting the
+    /// client to tell us which one it was.
+    async fn put_sso_state(&self, state: &str, session_id: &str) -> Result<(), ProxyError>;
+
+    /// Consume an SSO `state` value. Returns the session id it was bound to,
+    /// or `None` if the state is unknown or was already used - either way
+    /// the caller must treat the callback as invalid.
+    async fn take_sso_state(&self, state: &str) -> Result<Option<String>, ProxyError>;
+
+    /// Record a login token as spent, keeping the dedup record around until
+    /// `expires_at` (the token's own `exp` claim) rather than this store's
+    /// generic TTL - a token deliberately minted to outlive the session TTL
+    /// must stay single-use for as long as it remains valid. Returns `true`
+    /// if this call is the first to consume it, `false` if it had already
+    /// been used - tokens are single use regardless of which session
+    /// presents them.
+    async fn consume_token(&self, token_id: &str, expires_at: u64) -> Result<bool, ProxyError>;
+
+    /// Durably log a terms acceptance under `subject`. The terms stage
+    /// passes the UIA session id, since the proxy doesn't decode access
+    /// tokens itself and so has no Matrix user id to key it by.
+    /// Unlike [`UiaSession`], these records outlive the session that
+    /// created them and aren't subject to its TTL/eviction.
+    async fn record_terms_acceptance(&self, subject: &str, acceptance: TermsAcceptance) -> Result<(), ProxyError>;
+
+    /// All terms acceptances logged for `subject`, oldest first.
+    async fn terms_acceptances(&self, subject: &str) -> Result<Vec<TermsAcceptance>, ProxyError>;
+}
+
+/// A value plus the timestamp it should be treated as gone at, so the
+/// in-memory store can honor a TTL the same way the Redis-backed one does
+/// via `EX`/`GETDEL`, just checked lazily on access instead of by Redis
+/// itself.
+struct Expiring<T> {
+    value: T,
+    expires_at: u64,
+}
+
+impl<T> Expiring<T> {
+    fn is_expired(&self) -> bool {
+        now() >= self.expires_at
+    }
+}
+
+#[derive(Default)]
+struct MemoryState {
+    sessions: HashMap<String, Expiring<UiaSession>>,
+    sso_states: HashMap<String, Expiring<String>>,
+    consumed_tokens: HashMap<String, u64>,
+    /// Durable - not subject to `ttl_seconds`, same as the Redis store's
+    /// un-expiring `RPUSH`ed acceptance records.
+    terms_acceptances: HashMap<String, Vec<TermsAcceptance>>,
+}
+
+/// Default, single-process [`SessionStore`]. Fine for a standalone
+/// deployment; swap in the Redis-backed store for anything running more
+/// than one replica.
+pub struct InMemorySessionStore {
+    ttl_seconds: u64,
+    state: Mutex<MemoryState>,
+}
+
+impl InMemorySessionStore {
+    pub fn new(ttl_seconds: u64) -> Self {
+        Self { ttl_seconds, state: Mutex::default() }
+    }
+
+    fn expires_at(&self) -> u64 {
+        now() + self.ttl_seconds
+    }
+}
+
+#[async_trait]
+impl SessionStore for InMemorySessionStore {
+    async fn create(&self, flows: Vec<Flow>, params: BTreeMap<String, Value>) -> Result<UiaSession, ProxyError> {
+        let session = UiaSession::new(flows, params);
+        let mut state = self.state.lock().expect("session store mutex poisoned");
+        state
+            .sessions
+            .insert(session.id.clone(), Expiring { value: session.clone(), expires_at: self.expires_at() });
+        Ok(session)
+    }
+
+    async fn get(&self, session_id: &str) -> Result<Option<UiaSession>, ProxyError> {
+        let mut state = self.state.lock().expect("session store mutex poisoned");
+        match state.sessions.get(session_id) {
+            Some(entry) if entry.is_expired() => {
+                state.sessions.remove(session_id);
+                Ok(None)
+            }
+            Some(entry) => Ok(Some(entry.value.clone())),
+            None => Ok(None),
+        }
+    }
+
+    async fn save(&self, session: &UiaSession) -> Result<(), ProxyError> {
+        let mut state = self.state.lock().expect("session store mutex poisoned");
+        state
+            .sessions
+            .insert(session.id.clone(), Expiring { value: session.clone(), expires_at: self.expires_at() });
+        Ok(())
+    }
+
+    async fn put_sso_state(&self, state_token: &str, session_id: &str) -> Result<(), ProxyError> {
+        let mut state = self.state.lock().expect("session store mutex poisoned");
+        state.sso_states.insert(
+            state_token.to_owned(),
+            Expiring { value: session_id.to_owned(), expires_at: self.expires_at() },
+        );
+        Ok(())
+    }
+
+    async fn take_sso_state(&self, state_token: &str) -> Result<Option<String>, ProxyError> {
+        let mut state = self.state.lock().expect("session store mutex poisoned");
+        Ok(state.sso_states.remove(state_token).filter(|entry| !entry.is_expired()).map(|entry| entry.value))
+    }
+
+    async fn consume_token(&self, token_id: &str, expires_at: u64) -> Result<bool, ProxyError> {
+        let mut state = self.state.lock().expect("session store mutex poisoned");
+        let already_consumed =
+            state.consumed_tokens.get(token_id).is_some_and(|recorded_expiry| now() < *recorded_expiry);
+        if already_consumed {
+            return Ok(false);
+        }
+        state.consumed_tokens.insert(token_id.to_owned(), expires_at);
+        Ok(true)
+    }
+
+    async fn record_terms_acceptance(&self, subject: &str, acceptance: TermsAcceptance) -> Result<(), ProxyError> {
+        let mut state = self.state.lock().expect("session store mutex poisoned");
+        state.terms_acceptances.entry(subject.to_owned()).or_default().push(acceptance);
+        Ok(())
+    }
+
+    async fn terms_acceptances(&self, subject: &str) -> Result<Vec<TermsAcceptance>, ProxyError> {
+        let state = self.state.lock().expect("session store mutex poisoned");
+        Ok(state.terms_acceptances.get(subject).cloned().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sso_state_can_only_be_taken_once() {
+        let store = InMemorySessionStore::new(3600);
+        store.put_sso_state("state-1", "session-1").await.unwrap();
+
+        assert_eq!(store.take_sso_state("state-1").await.unwrap(), Some("session-1".to_owned()));
+        assert_eq!(store.take_sso_state("state-1").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn unknown_sso_state_is_none() {
+        let store = InMemorySessionStore::new(3600);
+        assert_eq!(store.take_sso_state("never-issued").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn a_token_can_only_be_consumed_once() {
+        let store = InMemorySessionStore::new(3600);
+        let expires_at = now() + 3600;
+        assert!(store.consume_token("tok-1", expires_at).await.unwrap());
+        assert!(!store.consume_token("tok-1", expires_at).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn a_token_outliving_the_store_ttl_stays_single_use_until_its_own_expiry() {
+        // The store's generic TTL is shorter than the token's own `exp` -
+        // the dedup record must still be keyed by the token's expiry, not
+        // the store's, or a long-lived token could be replayed once the
+        // store's TTL elapses.
+        let store = InMemorySessionStore::new(0);
+        let expires_at = now() + 3600;
+        assert!(store.consume_token("tok-1", expires_at).await.unwrap());
+        assert!(!store.consume_token("tok-1", expires_at).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn terms_acceptances_accumulate_per_subject_and_are_queryable() {
+        let store = InMemorySessionStore::new(3600);
+        let acceptance = TermsAcceptance {
+            policy_id: "privacy_policy".to_owned(),
+            version: "1.0".to_owned(),
+            accepted_at: 0,
+        };
+        store.record_terms_acceptance("@alice:example.com", acceptance.clone()).await.unwrap();
+
+        assert_eq!(store.terms_acceptances("@alice:example.com").await.unwrap(), vec![acceptance]);
+        assert_eq!(store.terms_acceptances("@bob:example.com").await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn a_session_past_its_ttl_is_treated_as_gone() {
+        let store = InMemorySessionStore::new(0);
+        let session = store.create(vec![], BTreeMap::new()).await.unwrap();
+
+        assert!(store.get(&session.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn an_sso_state_past_its_ttl_is_treated_as_gone() {
+        let store = InMemorySessionStore::new(0);
+        store.put_sso_state("state-1", "session-1").await.unwrap();
+
+        assert_eq!(store.take_sso_state("state-1").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn a_consumed_token_past_its_own_expiry_can_be_consumed_again() {
+        let store = InMemorySessionStore::new(3600);
+        let already_expired = now().saturating_sub(1);
+        assert!(store.consume_token("tok-1", already_expired).await.unwrap());
+        assert!(store.consume_token("tok-1", already_expired).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn session_completion_only_requires_one_flow_to_be_satisfied() {
+        let store = InMemorySessionStore::new(3600);
+        let mut session = store
+            .create(
+                vec![Flow::new(["m.login.dummy"]), Flow::new(["m.login.terms", "m.login.dummy"])],
+                BTreeMap::new(),
+            )
+            .await
+            .unwrap();
+
+        assert!(!session.is_complete());
+        session.complete_stage("m.login.dummy");
+        assert!(session.is_complete());
+    }
+}
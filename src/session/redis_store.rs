@@ -0,0 +1,149 @@
+//! Redis-backed [`SessionStore`], so a UIA session survives across
+//! stateless replicas behind a load balancer instead of being pinned to
+//! whichever process first created it.
+
+use async_trait::async_trait;
+use redis::AsyncCommands;
+
+use super::{now, SessionStore, TermsAcceptance, UiaSession};
+use crate::{error::ProxyError, uiaa::Flow};
+
+fn session_key(id: &str) -> String {
+    format!("uia-proxy:session:{}", id)
+}
+
+fn sso_state_key(state: &str) -> String {
+    format!("uia-proxy:sso-state:{}", state)
+}
+
+fn token_key(token_id: &str) -> String {
+    format!("uia-proxy:consumed-token:{}", token_id)
+}
+
+fn terms_acceptances_key(subject: &str) -> String {
+    format!("uia-proxy:terms-acceptances:{}", subject)
+}
+
+pub struct RedisSessionStore {
+    client: redis::Client,
+    ttl_seconds: u64,
+}
+
+impl RedisSessionStore {
+    pub fn new(url: &str, ttl_seconds: u64) -> Result<Self, ProxyError> {
+        let client = redis::Client::open(url).map_err(|err| ProxyError::SessionStore(err.to_string()))?;
+        Ok(Self { client, ttl_seconds })
+    }
+
+    async fn connection(&self) -> Result<redis::aio::MultiplexedConnection, ProxyError> {
+        self.client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|err| ProxyError::SessionStore(err.to_string()))
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn create(
+        &self,
+        flows: Vec<Flow>,
+        params: std::collections::BTreeMap<String, serde_json::Value>,
+    ) -> Result<UiaSession, ProxyError> {
+        let session = UiaSession::new(flows, params);
+        self.save(&session).await?;
+        Ok(session)
+    }
+
+    async fn get(&self, session_id: &str) -> Result<Option<UiaSession>, ProxyError> {
+        let mut conn = self.connection().await?;
+        let raw: Option<String> = conn
+            .get(session_key(session_id))
+            .await
+            .map_err(|err| ProxyError::SessionStore(err.to_string()))?;
+        raw.map(|raw| serde_json::from_str(&raw).map_err(|err| ProxyError::SessionStore(err.to_string())))
+            .transpose()
+    }
+
+    async fn save(&self, session: &UiaSession) -> Result<(), ProxyError> {
+        let mut conn = self.connection().await?;
+        let raw = serde_json::to_string(session).map_err(|err| ProxyError::SessionStore(err.to_string()))?;
+        conn.set_ex::<_, _, ()>(session_key(&session.id), raw, self.ttl_seconds)
+            .await
+            .map_err(|err| ProxyError::SessionStore(err.to_string()))
+    }
+
+    async fn put_sso_state(&self, state: &str, session_id: &str) -> Result<(), ProxyError> {
+        let mut conn = self.connection().await?;
+        conn.set_ex::<_, _, ()>(sso_state_key(state), session_id, self.ttl_seconds)
+            .await
+            .map_err(|err| ProxyError::SessionStore(err.to_string()))
+    }
+
+    async fn take_sso_state(&self, state: &str) -> Result<Option<String>, ProxyError> {
+        let mut conn = self.connection().await?;
+        // GETDEL so a state value can only ever be handed out once, even if
+        // two callback requests race each other.
+        redis::cmd("GETDEL")
+            .arg(sso_state_key(state))
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| ProxyError::SessionStore(err.to_string()))
+    }
+
+    async fn consume_token(&self, token_id: &str, expires_at: u64) -> Result<bool, ProxyError> {
+        let mut conn = self.connection().await?;
+        // Keyed by the token's own expiry, not `self.ttl_seconds` - a token
+        // minted to outlive this store's generic TTL must stay single-use
+        // for as long as it remains valid.
+        let ttl_seconds = expires_at.saturating_sub(now()).max(1);
+        let set: Option<String> = redis::cmd("SET")
+            .arg(token_key(token_id))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_seconds)
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| ProxyError::SessionStore(err.to_string()))?;
+        Ok(set.is_some())
+    }
+
+    async fn record_terms_acceptance(&self, subject: &str, acceptance: TermsAcceptance) -> Result<(), ProxyError> {
+        let mut conn = self.connection().await?;
+        let raw = serde_json::to_string(&acceptance).map_err(|err| ProxyError::SessionStore(err.to_string()))?;
+        // No TTL: unlike sessions, SSO states, and consumed tokens, an
+        // acceptance record is meant to outlive the UIA session that
+        // created it so it can still be queried long after.
+        conn.rpush::<_, _, ()>(terms_acceptances_key(subject), raw)
+            .await
+            .map_err(|err| ProxyError::SessionStore(err.to_string()))
+    }
+
+    async fn terms_acceptances(&self, subject: &str) -> Result<Vec<TermsAcceptance>, ProxyError> {
+        let mut conn = self.connection().await?;
+        let raw: Vec<String> = conn
+            .lrange(terms_acceptances_key(subject), 0, -1)
+            .await
+            .map_err(|err| ProxyError::SessionStore(err.to_string()))?;
+        raw.iter()
+            .map(|entry| serde_json::from_str(entry).map_err(|err| ProxyError::SessionStore(err.to_string())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_namespaced_per_record_kind_so_they_cannot_collide() {
+        assert_eq!(session_key("abc"), "uia-proxy:session:abc");
+        assert_eq!(sso_state_key("abc"), "uia-proxy:sso-state:abc");
+        assert_eq!(token_key("abc"), "uia-proxy:consumed-token:abc");
+        assert_eq!(terms_acceptances_key("abc"), "uia-proxy:terms-acceptances:abc");
+        assert_ne!(session_key("abc"), sso_state_key("abc"));
+        assert_ne!(session_key("abc"), token_key("abc"));
+        assert_ne!(session_key("abc"), terms_acceptances_key("abc"));
+    }
+}
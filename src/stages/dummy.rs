@@ -0,0 +1,33 @@
+//! `m.login.dummy`: no verification at all, just a placeholder stage that
+//! always succeeds - used to round out registration flows that don't need
+//! a "real" stage (e.g. `m.login.dummy` on its own, or alongside
+//! `m.login.terms`).
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::Stage;
+use crate::{
+    error::ProxyError,
+    session::{SessionStore, UiaSession},
+};
+
+#[derive(Default)]
+pub struct DummyStage;
+
+#[async_trait]
+impl Stage for DummyStage {
+    fn stage_type(&self) -> &'static str {
+        "m.login.dummy"
+    }
+
+    async fn submit(
+        &self,
+        session: &mut UiaSession,
+        _auth: &Value,
+        _store: &dyn SessionStore,
+    ) -> Result<(), ProxyError> {
+        session.complete_stage(self.stage_type());
+        Ok(())
+    }
+}
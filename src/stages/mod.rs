@@ -0,0 +1,43 @@
+//! UIA stage implementations.
+//!
+//! A [`Stage`] only has to know how to validate one `auth` submission and,
+//! optionally, what to publish under its name in `UiaaInfo.params`. Session
+//! lookup, flow-completion bookkeeping, and the HTTP plumbing all live
+//! outside the stage so new ones stay this small.
+
+pub mod dummy;
+pub mod password;
+pub mod sso;
+pub mod terms;
+pub mod token;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::{
+    error::ProxyError,
+    session::{SessionStore, UiaSession},
+};
+
+#[async_trait]
+pub trait Stage: Send + Sync {
+    /// The `m.login.*` identifier this stage implements.
+    fn stage_type(&self) -> &'static str;
+
+    /// Extra data to publish under this stage's key in `UiaaInfo.params`,
+    /// e.g. an IdP's authorize URL or a policy document's version.
+    fn params(&self) -> Option<Value> {
+        None
+    }
+
+    /// Validate one `auth` submission for this stage. Implementations
+    /// should mutate `session` (e.g. `session.complete_stage(...)`) only on
+    /// success. `store` is handed through for stages that need state that
+    /// outlives or crosses a single session, e.g. single-use token ids.
+    async fn submit(
+        &self,
+        session: &mut UiaSession,
+        auth: &Value,
+        store: &dyn SessionStore,
+    ) -> Result<(), ProxyError>;
+}
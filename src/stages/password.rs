@@ -0,0 +1,65 @@
+//! `m.login.password`: delegate credential checking to the real homeserver
+//! rather than have the proxy ever see a validated password store.
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::Stage;
+use crate::{
+    error::ProxyError,
+    session::{SessionStore, UiaSession},
+};
+
+pub struct PasswordStage {
+    client: reqwest::Client,
+    homeserver_base_url: String,
+}
+
+impl PasswordStage {
+    pub fn new(client: reqwest::Client, homeserver_base_url: String) -> Self {
+        Self { client, homeserver_base_url }
+    }
+}
+
+#[async_trait]
+impl Stage for PasswordStage {
+    fn stage_type(&self) -> &'static str {
+        "m.login.password"
+    }
+
+    async fn submit(
+        &self,
+        session: &mut UiaSession,
+        auth: &Value,
+        _store: &dyn SessionStore,
+    ) -> Result<(), ProxyError> {
+        let identifier = auth
+            .get("identifier")
+            .cloned()
+            .ok_or_else(|| ProxyError::Forbidden("missing identifier".to_owned()))?;
+        let password = auth
+            .get("password")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ProxyError::Forbidden("missing password".to_owned()))?;
+
+        let login_request = json!({
+            "type": "m.login.password",
+            "identifier": identifier,
+            "password": password,
+        });
+
+        let res = self
+            .client
+            .post(format!("{}/_matrix/client/r0/login", self.homeserver_base_url))
+            .json(&login_request)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(ProxyError::Forbidden("invalid credentials".to_owned()));
+        }
+
+        session.complete_stage(self.stage_type());
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+//! `m.login.sso`: complete the stage via a browser redirect to an external
+//! IdP rather than a client-submitted `auth` body, mirroring the SSO
+//! loopback dance matrix-rust-sdk drives for `m.login.sso` during login.
+//!
+//! The stage itself only has to answer "is this session done", which
+//! [`SsoStage::submit`] reads off state the `/callback` handler (see
+//! [`crate::handlers::sso`]) sets directly on the session.
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use url::Url;
+
+use super::Stage;
+use crate::{
+    config::SsoConfig,
+    error::ProxyError,
+    session::{SessionStore, UiaSession},
+};
+
+/// Marker stashed in [`UiaSession::state`] once the IdP callback has been
+/// validated for this session.
+const VERIFIED_KEY: &str = "m.login.sso.verified";
+
+pub struct SsoStage {
+    config: SsoConfig,
+}
+
+impl SsoStage {
+    pub fn new(config: SsoConfig) -> Self {
+        Self { config }
+    }
+
+    /// Build the URL the `/fallback/web` handler should 302 to, carrying a
+    /// freshly generated opaque `state` value the caller is responsible for
+    /// tying back to the session via `SessionStore::put_sso_state`.
+    pub fn authorize_url(&self, state: &str) -> Result<Url, ProxyError> {
+        let mut url = Url::parse(&self.config.authorize_url)
+            .map_err(|err| ProxyError::Config(err.to_string()))?;
+        url.query_pairs_mut()
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("response_type", "code")
+            .append_pair("redirect_uri", &self.callback_url())
+            .append_pair("state", state);
+        Ok(url)
+    }
+
+    pub fn callback_url(&self) -> String {
+        format!("{}/_matrix/client/r0/auth/m.login.sso/callback", self.config.redirect_base)
+    }
+
+    /// Exchange an authorization `code` from the IdP callback for proof the
+    /// user authenticated, the way matrix-rust-sdk exchanges an SSO
+    /// `loginToken` for a real session.
+    pub async fn exchange_code(&self, client: &reqwest::Client, code: &str) -> Result<(), ProxyError> {
+        let res = client
+            .post(&self.config.token_url)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+                ("redirect_uri", &self.callback_url()),
+            ])
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(ProxyError::Forbidden("IdP rejected the authorization code".to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn mark_verified(session: &mut UiaSession) {
+        session.state.insert(VERIFIED_KEY.to_owned(), json!(true));
+        session.complete_stage("m.login.sso");
+    }
+}
+
+#[async_trait]
+impl Stage for SsoStage {
+    fn stage_type(&self) -> &'static str {
+        "m.login.sso"
+    }
+
+    async fn submit(
+        &self,
+        session: &mut UiaSession,
+        _auth: &Value,
+        _store: &dyn SessionStore,
+    ) -> Result<(), ProxyError> {
+        let verified = session.state.get(VERIFIED_KEY).and_then(Value::as_bool).unwrap_or(false);
+        if !verified {
+            return Err(ProxyError::Forbidden(
+                "complete the m.login.sso/fallback/web redirect first".to_owned(),
+            ));
+        }
+        session.complete_stage(self.stage_type());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SsoConfig {
+        SsoConfig {
+            authorize_url: "https://idp.example.com/authorize".to_owned(),
+            token_url: "https://idp.example.com/token".to_owned(),
+            client_id: "proxy-client".to_owned(),
+            client_secret: "secret".to_owned(),
+            redirect_base: "https://proxy.example.com".to_owned(),
+        }
+    }
+
+    #[test]
+    fn authorize_url_carries_the_state_and_redirect_uri() {
+        let stage = SsoStage::new(config());
+        let url = stage.authorize_url("opaque-state").unwrap();
+
+        let pairs: Vec<_> = url.query_pairs().collect();
+        assert!(pairs.iter().any(|(k, v)| k == "state" && v == "opaque-state"));
+        assert!(pairs.iter().any(|(k, v)| k == "redirect_uri" && v.contains("/callback")));
+    }
+
+    #[tokio::test]
+    async fn submit_fails_until_the_callback_has_verified_the_session() {
+        let stage = SsoStage::new(config());
+        let store = crate::session::InMemorySessionStore::new(3600);
+        let mut session = UiaSession::new(vec![], Default::default());
+        assert!(stage.submit(&mut session, &json!({}), &store).await.is_err());
+
+        SsoStage::mark_verified(&mut session);
+        assert!(stage.submit(&mut session, &json!({}), &store).await.is_ok());
+    }
+}
@@ -0,0 +1,167 @@
+//! `m.login.terms`: publish the configured policy documents and only
+//! accept a submission if the session was actually offered the version
+//! currently in force - a version bump in config invalidates any session
+//! that was challenged before the bump, forcing re-acceptance.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::Stage;
+use crate::{
+    config::TermsConfig,
+    error::ProxyError,
+    session::{SessionStore, TermsAcceptance, UiaSession},
+};
+
+/// Where accepted `(policy id -> version)` pairs are recorded on the
+/// session once `m.login.terms` completes.
+const ACCEPTED_KEY: &str = "m.login.terms.accepted";
+
+pub struct TermsStage {
+    config: TermsConfig,
+}
+
+impl TermsStage {
+    pub fn new(config: TermsConfig) -> Self {
+        Self { config }
+    }
+
+    fn policies_value(&self) -> Value {
+        let policies: BTreeMap<String, Value> = self
+            .config
+            .policies
+            .iter()
+            .map(|(id, policy)| {
+                let mut entry = serde_json::Map::new();
+                entry.insert("version".to_owned(), json!(policy.version));
+                for (lang, translation) in &policy.languages {
+                    entry.insert(lang.clone(), json!({ "name": translation.name, "url": translation.url }));
+                }
+                (id.clone(), Value::Object(entry))
+            })
+            .collect();
+        json!({ "policies": policies })
+    }
+
+    fn served_version<'a>(params: &'a Value, policy_id: &str) -> Option<&'a str> {
+        params.get("policies")?.get(policy_id)?.get("version")?.as_str()
+    }
+}
+
+#[async_trait]
+impl Stage for TermsStage {
+    fn stage_type(&self) -> &'static str {
+        "m.login.terms"
+    }
+
+    fn params(&self) -> Option<Value> {
+        Some(self.policies_value())
+    }
+
+    async fn submit(
+        &self,
+        session: &mut UiaSession,
+        _auth: &Value,
+        store: &dyn SessionStore,
+    ) -> Result<(), ProxyError> {
+        let served = session.params.get(self.stage_type()).cloned().unwrap_or_else(|| self.policies_value());
+
+        for (policy_id, policy) in &self.config.policies {
+            if Self::served_version(&served, policy_id) != Some(policy.version.as_str()) {
+                return Err(ProxyError::Forbidden(format!(
+                    "the {} policy has changed since this session's challenge was issued; request a fresh one",
+                    policy_id
+                )));
+            }
+        }
+
+        let accepted: BTreeMap<String, String> =
+            self.config.policies.iter().map(|(id, policy)| (id.clone(), policy.version.clone())).collect();
+        session.state.insert(ACCEPTED_KEY.to_owned(), json!(accepted));
+
+        // Logged under the session id - the proxy never decodes the
+        // client's access token, so it has no Matrix user id to key this
+        // durable record by. Unlike `session.state` above, this survives
+        // the session itself being evicted.
+        for (policy_id, version) in &accepted {
+            store
+                .record_terms_acceptance(
+                    &session.id,
+                    TermsAcceptance {
+                        policy_id: policy_id.clone(),
+                        version: version.clone(),
+                        accepted_at: crate::session::now(),
+                    },
+                )
+                .await?;
+        }
+
+        session.complete_stage(self.stage_type());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap as Map;
+
+    fn config(version: &str) -> TermsConfig {
+        let mut languages = Map::new();
+        languages.insert(
+            "en".to_owned(),
+            crate::config::PolicyTranslation {
+                name: "Privacy Policy".to_owned(),
+                url: "https://example.com/privacy".to_owned(),
+            },
+        );
+        let mut policies = Map::new();
+        policies.insert(
+            "privacy_policy".to_owned(),
+            crate::config::PolicyConfig { version: version.to_owned(), languages },
+        );
+        TermsConfig { policies }
+    }
+
+    #[tokio::test]
+    async fn accepting_the_currently_served_version_completes_the_stage() {
+        let stage = TermsStage::new(config("1.0"));
+        let store = crate::session::InMemorySessionStore::new(3600);
+        let mut session = UiaSession::new(vec![], BTreeMap::new());
+        session.params.insert(stage.stage_type().to_owned(), stage.policies_value());
+
+        assert!(stage.submit(&mut session, &json!({}), &store).await.is_ok());
+        assert!(session.completed.iter().any(|s| s == "m.login.terms"));
+    }
+
+    #[tokio::test]
+    async fn accepting_durably_logs_the_acceptance_under_the_session_id() {
+        let stage = TermsStage::new(config("1.0"));
+        let store = crate::session::InMemorySessionStore::new(3600);
+        let mut session = UiaSession::new(vec![], BTreeMap::new());
+        session.params.insert(stage.stage_type().to_owned(), stage.policies_value());
+
+        stage.submit(&mut session, &json!({}), &store).await.unwrap();
+
+        let acceptances = store.terms_acceptances(&session.id).await.unwrap();
+        assert_eq!(acceptances.len(), 1);
+        assert_eq!(acceptances[0].policy_id, "privacy_policy");
+        assert_eq!(acceptances[0].version, "1.0");
+    }
+
+    #[tokio::test]
+    async fn a_version_bump_after_the_session_was_issued_is_rejected() {
+        let stage_v1 = TermsStage::new(config("1.0"));
+        let store = crate::session::InMemorySessionStore::new(3600);
+        let mut session = UiaSession::new(vec![], BTreeMap::new());
+        // The session was challenged while version 1.0 was in force...
+        session.params.insert(stage_v1.stage_type().to_owned(), stage_v1.policies_value());
+
+        // ...but the operator bumped the policy before the client replayed.
+        let stage_v2 = TermsStage::new(config("2.0"));
+        assert!(stage_v2.submit(&mut session, &json!({}), &store).await.is_err());
+        assert!(!session.completed.iter().any(|s| s == "m.login.terms"));
+    }
+}
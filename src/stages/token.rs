@@ -0,0 +1,201 @@
+//! `m.login.token`: validate an externally issued JWT (e.g. minted by a
+//! support tool or another service that already knows who the user is)
+//! rather than prompt for credentials again. `exp` is enforced by the JWT
+//! itself, and the `jti` claim is consumed from the [`SessionStore`] so a
+//! token can't be replayed, even against an unrelated session.
+
+use async_trait::async_trait;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::Stage;
+use crate::{
+    config::TokenConfig,
+    error::ProxyError,
+    session::{SessionStore, UiaSession},
+};
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    jti: String,
+    exp: u64,
+}
+
+pub struct TokenStage {
+    config: TokenConfig,
+    http_client: reqwest::Client,
+}
+
+impl TokenStage {
+    pub fn new(config: TokenConfig, http_client: reqwest::Client) -> Self {
+        Self { config, http_client }
+    }
+
+    async fn decode(&self, token: &str) -> Result<Claims, ProxyError> {
+        match &self.config {
+            TokenConfig::Hmac { secret, issuer, audience } => {
+                let key = DecodingKey::from_secret(secret.as_bytes());
+                let mut validation = Validation::new(Algorithm::HS256);
+                if let Some(issuer) = issuer {
+                    validation.set_issuer(&[issuer]);
+                    validation.required_spec_claims.insert("iss".to_owned());
+                }
+                if let Some(audience) = audience {
+                    validation.set_audience(&[audience]);
+                    validation.required_spec_claims.insert("aud".to_owned());
+                }
+                decode::<Claims>(token, &key, &validation)
+                    .map(|data| data.claims)
+                    .map_err(|_| ProxyError::Forbidden("invalid or expired token".to_owned()))
+            }
+            TokenConfig::Jwks { jwks_url, issuer, audience } => {
+                let header = decode_header(token)
+                    .map_err(|_| ProxyError::Forbidden("invalid or expired token".to_owned()))?;
+                let kid = header
+                    .kid
+                    .as_ref()
+                    .ok_or_else(|| ProxyError::Forbidden("invalid or expired token".to_owned()))?;
+
+                let jwks: JwkSet = self
+                    .http_client
+                    .get(jwks_url)
+                    .send()
+                    .await
+                    .map_err(ProxyError::Upstream)?
+                    .json()
+                    .await
+                    .map_err(ProxyError::Upstream)?;
+                let jwk = jwks
+                    .find(kid)
+                    .ok_or_else(|| ProxyError::Forbidden("invalid or expired token".to_owned()))?;
+                let key = DecodingKey::from_jwk(jwk)
+                    .map_err(|_| ProxyError::Forbidden("invalid or expired token".to_owned()))?;
+
+                let mut validation = Validation::new(header.alg);
+                validation.set_issuer(&[issuer]);
+                validation.set_audience(&[audience]);
+                validation.required_spec_claims.insert("iss".to_owned());
+                validation.required_spec_claims.insert("aud".to_owned());
+                decode::<Claims>(token, &key, &validation)
+                    .map(|data| data.claims)
+                    .map_err(|_| ProxyError::Forbidden("invalid or expired token".to_owned()))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Stage for TokenStage {
+    fn stage_type(&self) -> &'static str {
+        "m.login.token"
+    }
+
+    async fn submit(
+        &self,
+        session: &mut UiaSession,
+        auth: &Value,
+        store: &dyn SessionStore,
+    ) -> Result<(), ProxyError> {
+        let token = auth
+            .get("token")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ProxyError::Forbidden("missing token".to_owned()))?;
+
+        let claims = self.decode(token).await?;
+
+        if !store.consume_token(&claims.jti, claims.exp).await? {
+            return Err(ProxyError::Forbidden("token already used".to_owned()));
+        }
+
+        session.complete_stage(self.stage_type());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::session::InMemorySessionStore;
+
+    #[derive(Serialize)]
+    struct SignedClaims {
+        jti: String,
+        exp: u64,
+    }
+
+    fn config() -> TokenConfig {
+        TokenConfig::Hmac { secret: "test-secret".to_owned(), issuer: None, audience: None }
+    }
+
+    fn stage() -> TokenStage {
+        TokenStage::new(config(), reqwest::Client::new())
+    }
+
+    fn token(jti: &str, secret: &str) -> String {
+        let exp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + 3600;
+        encode(
+            &Header::new(Algorithm::HS256),
+            &SignedClaims { jti: jti.to_owned(), exp },
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_malformed_token_is_rejected() {
+        let stage = stage();
+        let store = InMemorySessionStore::new(3600);
+        let mut session = UiaSession::new(vec![], Default::default());
+
+        assert!(stage.submit(&mut session, &json!({ "token": "not-a-jwt" }), &store).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn a_token_signed_with_the_wrong_secret_is_rejected() {
+        let stage = stage();
+        let store = InMemorySessionStore::new(3600);
+        let mut session = UiaSession::new(vec![], Default::default());
+        let jwt = token("tok-1", "wrong-secret");
+
+        assert!(stage.submit(&mut session, &json!({ "token": jwt }), &store).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn a_valid_token_completes_the_stage_but_only_once() {
+        let stage = stage();
+        let store = InMemorySessionStore::new(3600);
+        let jwt = token("tok-2", "test-secret");
+
+        let mut session = UiaSession::new(vec![], Default::default());
+        assert!(stage.submit(&mut session, &json!({ "token": jwt }), &store).await.is_ok());
+        assert!(session.completed.iter().any(|s| s == "m.login.token"));
+
+        let mut other_session = UiaSession::new(vec![], Default::default());
+        let err = stage.submit(&mut other_session, &json!({ "token": jwt }), &store).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Forbidden(_)));
+    }
+
+    #[tokio::test]
+    async fn a_token_without_the_required_issuer_is_rejected() {
+        let config = TokenConfig::Hmac {
+            secret: "test-secret".to_owned(),
+            issuer: Some("https://issuer.example.com".to_owned()),
+            audience: None,
+        };
+        let stage = TokenStage::new(config, reqwest::Client::new());
+        let store = InMemorySessionStore::new(3600);
+        let mut session = UiaSession::new(vec![], Default::default());
+        // Signed correctly but with no `iss` claim at all.
+        let jwt = token("tok-3", "test-secret");
+
+        let err = stage.submit(&mut session, &json!({ "token": jwt }), &store).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Forbidden(_)));
+    }
+}
@@ -0,0 +1,80 @@
+//! Shared state handed to every handler.
+
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    config::Config,
+    session::SessionStore,
+    stages::{
+        dummy::DummyStage, password::PasswordStage, sso::SsoStage, terms::TermsStage,
+        token::TokenStage, Stage,
+    },
+};
+
+pub struct AppState {
+    pub config: Config,
+    pub session_store: Arc<dyn SessionStore>,
+    pub stages: HashMap<&'static str, Arc<dyn Stage>>,
+    pub http_client: reqwest::Client,
+    /// Kept around in its concrete type so the `/fallback/web` and
+    /// `/callback` handlers can build IdP URLs and exchange codes without
+    /// downcasting out of `stages`.
+    pub sso_stage: Option<Arc<SsoStage>>,
+}
+
+impl AppState {
+    pub fn new(config: Config, session_store: Arc<dyn SessionStore>) -> Self {
+        let http_client = reqwest::Client::new();
+        let mut stages: HashMap<&'static str, Arc<dyn Stage>> = HashMap::new();
+
+        let password_stage =
+            Arc::new(PasswordStage::new(http_client.clone(), config.homeserver.base_url.clone()));
+        stages.insert(password_stage.stage_type(), password_stage);
+
+        let dummy_stage = Arc::new(DummyStage);
+        stages.insert(dummy_stage.stage_type(), dummy_stage);
+
+        let terms_stage = Arc::new(TermsStage::new(config.terms.clone()));
+        stages.insert(terms_stage.stage_type(), terms_stage);
+
+        let sso_stage = config.sso.clone().map(|sso_config| Arc::new(SsoStage::new(sso_config)));
+        if let Some(sso_stage) = sso_stage.clone() {
+            stages.insert(sso_stage.stage_type(), sso_stage);
+        }
+
+        if let Some(token_config) = config.token.clone() {
+            let token_stage = Arc::new(TokenStage::new(token_config, http_client.clone()));
+            stages.insert(token_stage.stage_type(), token_stage);
+        }
+
+        Self { config, session_store, stages, http_client, sso_stage }
+    }
+
+    /// Parameters every currently registered stage wants published under
+    /// `UiaaInfo.params` when a fresh challenge is issued.
+    pub fn stage_params(&self) -> crate::config::StageParams {
+        self.stages
+            .values()
+            .filter_map(|stage| stage.params().map(|params| (stage.stage_type().to_owned(), params)))
+            .collect()
+    }
+
+    /// Flows offered for the login-style endpoints, with `m.login.sso`
+    /// and/or `m.login.token` alternatives folded in automatically whenever
+    /// they're configured so operators don't have to repeat them in every
+    /// configured flow.
+    pub fn login_flows(&self) -> Vec<crate::uiaa::Flow> {
+        let mut flows = self.config.login_flows();
+        if self.sso_stage.is_some()
+            && !flows.iter().any(|flow| flow.stages == vec!["m.login.sso".to_owned()])
+        {
+            flows.push(crate::uiaa::Flow::new(["m.login.sso"]));
+        }
+        if self.stages.contains_key("m.login.token")
+            && !flows.iter().any(|flow| flow.stages == vec!["m.login.token".to_owned()])
+        {
+            flows.push(crate::uiaa::Flow::new(["m.login.token"]));
+        }
+        flows
+    }
+}
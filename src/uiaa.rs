@@ -0,0 +1,96 @@
+//! Matrix User-Interactive Authentication (UIA) wire types.
+//!
+//! These mirror the shapes defined by the Matrix spec
+//! (`m.login.*` stages, flows, and the `UiaaInfo` error body) closely enough
+//! to round-trip through `serde_json` without any proxy-specific fields
+//! leaking into the response.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One acceptable ordered sequence of stages.
+///
+/// A session is complete as soon as every stage in *any* flow has been
+/// completed, regardless of which other flows were offered alongside it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Flow {
+    pub stages: Vec<String>,
+}
+
+impl Flow {
+    pub fn new(stages: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self { stages: stages.into_iter().map(Into::into).collect() }
+    }
+
+    fn is_satisfied_by(&self, completed: &[String]) -> bool {
+        self.stages.iter().all(|stage| completed.contains(stage))
+    }
+}
+
+/// The body returned for a 401 UIA challenge, and embedded in subsequent
+/// challenges until every stage of some flow is completed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiaaInfo {
+    pub flows: Vec<Flow>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, Value>,
+    pub session: String,
+    #[serde(default)]
+    pub completed: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errcode: Option<String>,
+}
+
+impl UiaaInfo {
+    pub fn is_complete(&self) -> bool {
+        self.flows.iter().any(|flow| flow.is_satisfied_by(&self.completed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flow_is_satisfied_once_every_stage_is_completed() {
+        let flow = Flow::new(["m.login.terms", "m.login.dummy"]);
+        assert!(!flow.is_satisfied_by(&["m.login.terms".to_owned()]));
+        assert!(flow.is_satisfied_by(&[
+            "m.login.terms".to_owned(),
+            "m.login.dummy".to_owned()
+        ]));
+    }
+
+    #[test]
+    fn session_is_complete_if_any_offered_flow_is_satisfied() {
+        let info = UiaaInfo {
+            flows: vec![
+                Flow::new(["m.login.dummy"]),
+                Flow::new(["m.login.terms", "m.login.password"]),
+            ],
+            params: BTreeMap::new(),
+            session: "sess".to_owned(),
+            completed: vec!["m.login.dummy".to_owned()],
+            error: None,
+            errcode: None,
+        };
+        assert!(info.is_complete());
+    }
+
+    #[test]
+    fn session_is_incomplete_if_no_flow_is_fully_satisfied() {
+        let info = UiaaInfo {
+            flows: vec![Flow::new(["m.login.terms", "m.login.password"])],
+            params: BTreeMap::new(),
+            session: "sess".to_owned(),
+            completed: vec!["m.login.terms".to_owned()],
+            error: None,
+            errcode: None,
+        };
+        assert!(!info.is_complete());
+    }
+}
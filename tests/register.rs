@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use famedly_e2e_testing::{
+    assert_matches::assert_matches, eyre::Result, matrix_sdk, tokio, uuid::Uuid,
+    DEV_ENV_HOMESERVER,
+};
+
+#[tokio::test]
+async fn test_register_requires_uia() -> Result<()> {
+    let client = matrix_sdk::Client::new(DEV_ENV_HOMESERVER)?;
+
+    let username = format!("user-{}", Uuid::new_v4().to_hyphenated().to_string());
+    let mut request = matrix_sdk::api::r0::account::register::Request::new();
+    request.username = Some(&username);
+    request.password = Some("password");
+
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+
+    assert!(
+        uiaa_response
+            .flows
+            .iter()
+            .any(|flow| flow.stages.iter().any(|stage| stage == "m.login.dummy")),
+        "expected the registration flow to offer m.login.dummy"
+    );
+    assert!(uiaa_response.session.is_some(), "expected a fresh session id");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_register_completes_after_dummy_stage() -> Result<()> {
+    let client = matrix_sdk::Client::new(DEV_ENV_HOMESERVER)?;
+
+    let username = format!("user-{}", Uuid::new_v4().to_hyphenated().to_string());
+    let mut request = matrix_sdk::api::r0::account::register::Request::new();
+    request.username = Some(&username);
+    request.password = Some("password");
+
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+    let flow = uiaa_response
+        .flows
+        .iter()
+        .find(|flow| flow.stages == vec!["m.login.dummy".to_owned()])
+        .expect("expected a dummy-only flow to be offered for simple clients");
+
+    assert_eq!(flow.stages.len(), 1);
+
+    let mut request = matrix_sdk::api::r0::account::register::Request::new();
+    request.username = Some(&username);
+    request.password = Some("password");
+
+    let auth_parameters = BTreeMap::new();
+    let auth = matrix_sdk::api::r0::uiaa::AuthData::DirectRequest {
+        kind: "m.login.dummy",
+        session: uiaa_response.session.as_deref(),
+        auth_parameters,
+    };
+    request.auth = Some(auth);
+
+    let res = client.send(request, None).await?;
+
+    assert_matches!(res, matrix_sdk::api::r0::account::register::Response { .. });
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_register_stage_mismatch_with_login() -> Result<()> {
+    let client = matrix_sdk::Client::new(DEV_ENV_HOMESERVER)?;
+
+    let username = format!("user-{}", Uuid::new_v4().to_hyphenated().to_string());
+    let mut request = matrix_sdk::api::r0::account::register::Request::new();
+    request.username = Some(&username);
+    request.password = Some("password");
+
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+
+    assert!(
+        uiaa_response
+            .flows
+            .iter()
+            .all(|flow| !flow.stages.iter().any(|stage| stage == "m.login.password")),
+        "registration stages are configured separately from login stages, so \
+         m.login.password shouldn't be offered here unless explicitly configured"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use std::convert::TryInto;
+
+use famedly_e2e_testing::{
+    eyre::{eyre, Result},
+    matrix_sdk,
+    reqwest::{self, StatusCode},
+    tokio,
+    DEV_ENV_HOMESERVER,
+};
+
+#[tokio::test]
+async fn test_sso_stage_advertised_in_uiaa() -> Result<()> {
+    let client = matrix_sdk::Client::new(DEV_ENV_HOMESERVER.try_into()?)?;
+
+    let user = "@admin:dev.famedly.local";
+    let password = "password";
+    let device_id = "some_device";
+    client.login(user, password, device_id.into(), None).await?;
+
+    let request = matrix_sdk::api::r0::device::delete_device::Request::new(device_id.into());
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+
+    assert!(
+        uiaa_response
+            .flows
+            .iter()
+            .any(|flow| flow.stages.iter().any(|stage| stage == "m.login.sso")),
+        "expected m.login.sso to be advertised as a possible stage"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sso_fallback_redirects_to_idp() -> Result<()> {
+    let client = matrix_sdk::Client::new(DEV_ENV_HOMESERVER.try_into()?)?;
+
+    let user = "@admin:dev.famedly.local";
+    let password = "password";
+    let device_id = "some_device";
+    client.login(user, password, device_id.into(), None).await?;
+
+    let request = matrix_sdk::api::r0::device::delete_device::Request::new(device_id.into());
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+    let session = uiaa_response.session.clone().expect("expected a session id");
+
+    let fallback_url = format!(
+        "{}/_matrix/client/r0/auth/m.login.sso/fallback/web?session={}",
+        DEV_ENV_HOMESERVER, session
+    );
+
+    let client = reqwest::ClientBuilder::new()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+
+    let res = client.get(&fallback_url).send().await?;
+
+    assert_eq!(res.status(), StatusCode::FOUND);
+
+    let location = res
+        .headers()
+        .get("location")
+        .ok_or_else(|| eyre!("expected a location header"))?
+        .to_str()?
+        .to_owned();
+
+    assert!(
+        location.contains("state="),
+        "expected the redirect to the IdP to carry an opaque state parameter, got {}",
+        location
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sso_callback_rejects_unknown_state() -> Result<()> {
+    let callback_url = format!(
+        "{}/_matrix/client/r0/auth/m.login.sso/callback?state=not-a-real-state&code=irrelevant",
+        DEV_ENV_HOMESERVER
+    );
+
+    let res = reqwest::get(&callback_url).await?;
+
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+
+use famedly_e2e_testing::{
+    assert_matches::assert_matches, eyre::Result, matrix_sdk, tokio, uuid::Uuid,
+    DEV_ENV_HOMESERVER,
+};
+
+#[tokio::test]
+async fn test_terms_stage_publishes_policies() -> Result<()> {
+    let client = matrix_sdk::Client::new(DEV_ENV_HOMESERVER)?;
+
+    let username = format!("user-{}", Uuid::new_v4().to_hyphenated().to_string());
+    let mut request = matrix_sdk::api::r0::account::register::Request::new();
+    request.username = Some(&username);
+    request.password = Some("password");
+
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+
+    let policies = uiaa_response
+        .params
+        .get("m.login.terms")
+        .and_then(|params| params.get("policies"))
+        .and_then(|policies| policies.get("privacy_policy"))
+        .expect("expected a privacy_policy to be published under m.login.terms params");
+
+    assert!(policies.get("version").is_some());
+    let en = policies
+        .get("en")
+        .expect("expected at least an en translation");
+    assert!(en.get("name").is_some());
+    assert!(en.get("url").is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_terms_stage_completes_registration() -> Result<()> {
+    let client = matrix_sdk::Client::new(DEV_ENV_HOMESERVER)?;
+
+    let username = format!("user-{}", Uuid::new_v4().to_hyphenated().to_string());
+    let mut request = matrix_sdk::api::r0::account::register::Request::new();
+    request.username = Some(&username);
+    request.password = Some("password");
+
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+
+    let mut request = matrix_sdk::api::r0::account::register::Request::new();
+    request.username = Some(&username);
+    request.password = Some("password");
+
+    let auth_parameters = BTreeMap::new();
+    let auth = matrix_sdk::api::r0::uiaa::AuthData::DirectRequest {
+        kind: "m.login.terms",
+        session: uiaa_response.session.as_deref(),
+        auth_parameters,
+    };
+    request.auth = Some(auth);
+
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected, m.login.dummy is still outstanding");
+
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+
+    assert!(
+        uiaa_response
+            .completed
+            .iter()
+            .any(|stage| stage == "m.login.terms"),
+        "expected m.login.terms to be marked completed after acceptance"
+    );
+
+    let mut request = matrix_sdk::api::r0::account::register::Request::new();
+    request.username = Some(&username);
+    request.password = Some("password");
+
+    let auth_parameters = BTreeMap::new();
+    let auth = matrix_sdk::api::r0::uiaa::AuthData::DirectRequest {
+        kind: "m.login.dummy",
+        session: uiaa_response.session.as_deref(),
+        auth_parameters,
+    };
+    request.auth = Some(auth);
+
+    let res = client.send(request, None).await?;
+
+    assert_matches!(res, matrix_sdk::api::r0::account::register::Response { .. });
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_terms_version_is_pinned_to_the_session_that_was_served_it() -> Result<()> {
+    // The running dev environment doesn't expose a way to bump the terms
+    // config from a client, so this can't drive an actual version bump
+    // end-to-end. What it can pin is the precondition that makes the
+    // staleness check meaningful at all: the version recorded on a session
+    // is the one it was challenged with, not whatever the server happens to
+    // be configured with at submission time. The bump-invalidates-the-
+    // session behaviour itself is covered directly in
+    // stages::terms::tests::a_version_bump_after_the_session_was_issued_is_rejected,
+    // which can reconfigure the stage between the two calls.
+    let client = matrix_sdk::Client::new(DEV_ENV_HOMESERVER)?;
+
+    let username = format!("user-{}", Uuid::new_v4().to_hyphenated().to_string());
+    let mut request = matrix_sdk::api::r0::account::register::Request::new();
+    request.username = Some(&username);
+    request.password = Some("password");
+
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+    let session = uiaa_response.session.clone().expect("expected a session id");
+
+    let version_at_challenge = uiaa_response
+        .params
+        .get("m.login.terms")
+        .and_then(|params| params.get("policies"))
+        .and_then(|policies| policies.get("privacy_policy"))
+        .and_then(|policy| policy.get("version"))
+        .cloned()
+        .expect("expected the privacy_policy version to be published");
+
+    // Submitting m.login.terms against the same session later must still
+    // see the version it was originally challenged with.
+    let mut request = matrix_sdk::api::r0::account::register::Request::new();
+    request.username = Some(&username);
+    request.password = Some("password");
+
+    let auth_parameters = BTreeMap::new();
+    let auth = matrix_sdk::api::r0::uiaa::AuthData::DirectRequest {
+        kind: "m.login.terms",
+        session: Some(&session),
+        auth_parameters,
+    };
+    request.auth = Some(auth);
+
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected, m.login.dummy is still outstanding");
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+
+    let version_at_submit = uiaa_response
+        .params
+        .get("m.login.terms")
+        .and_then(|params| params.get("policies"))
+        .and_then(|policies| policies.get("privacy_policy"))
+        .and_then(|policy| policy.get("version"))
+        .cloned()
+        .expect("expected the privacy_policy version to still be published");
+
+    assert_eq!(version_at_challenge, version_at_submit);
+    assert!(
+        uiaa_response
+            .completed
+            .iter()
+            .any(|stage| stage == "m.login.terms"),
+        "accepting the version that was actually served must still succeed"
+    );
+
+    Ok(())
+}
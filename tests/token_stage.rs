@@ -0,0 +1,142 @@
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use famedly_e2e_testing::{
+    assert_matches::assert_matches, eyre::Result, matrix_sdk, serde_json::json, tokio,
+    uuid::Uuid, DEV_ENV_HOMESERVER, DEV_ENV_TOKEN_SECRET,
+};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Claims {
+    jti: String,
+    exp: u64,
+}
+
+/// Signs a token against the dev environment's configured `m.login.token`
+/// HMAC secret, the same way an external issuer would.
+fn sign(jti: &str) -> String {
+    let exp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + 3600;
+    encode(
+        &Header::new(Algorithm::HS256),
+        &Claims { jti: jti.to_owned(), exp },
+        &EncodingKey::from_secret(DEV_ENV_TOKEN_SECRET.as_bytes()),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_token_stage_rejects_unknown_token() -> Result<()> {
+    let client = matrix_sdk::Client::new(DEV_ENV_HOMESERVER)?;
+
+    let user = "@admin:dev.famedly.local";
+    let password = "password";
+    let device_id = "some_device";
+    client.login(user, password, device_id.into(), None).await?;
+
+    let request = matrix_sdk::api::r0::device::delete_device::Request::new(device_id.into());
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+
+    let mut request = matrix_sdk::api::r0::device::delete_device::Request::new(device_id.into());
+
+    let mut auth_parameters = BTreeMap::new();
+    auth_parameters.insert("token".to_owned(), json!("not-a-real-token"));
+
+    let auth = matrix_sdk::api::r0::uiaa::AuthData::DirectRequest {
+        kind: "m.login.token",
+        session: uiaa_response.session.as_deref(),
+        auth_parameters,
+    };
+    request.auth = Some(auth);
+
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("an invalid token must not complete the stage");
+
+    assert_matches!(
+        err,
+        matrix_sdk::Error::Http(matrix_sdk::HttpError::UiaaError(_))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_stage_rejects_reused_token() -> Result<()> {
+    let client = matrix_sdk::Client::new(DEV_ENV_HOMESERVER)?;
+
+    let user = "@admin:dev.famedly.local";
+    let password = "password";
+    let device_id = format!("token-reuse-{}", Uuid::new_v4().to_hyphenated());
+    client.login(user, password, &device_id, None).await?;
+
+    let jti = format!("jti-{}", Uuid::new_v4().to_hyphenated());
+    let token = sign(&jti);
+
+    // First use: a freshly issued token completes a brand new UIA session,
+    // letting this request through to the homeserver.
+    let request = matrix_sdk::api::r0::device::delete_device::Request::new((&device_id).into());
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+
+    let mut request = matrix_sdk::api::r0::device::delete_device::Request::new((&device_id).into());
+    let mut auth_parameters = BTreeMap::new();
+    auth_parameters.insert("token".to_owned(), json!(token));
+    request.auth = Some(matrix_sdk::api::r0::uiaa::AuthData::DirectRequest {
+        kind: "m.login.token",
+        session: uiaa_response.session.as_deref(),
+        auth_parameters,
+    });
+    client
+        .send(request, None)
+        .await
+        .expect("the first use of a freshly issued token must succeed");
+
+    // Second use: a different, unrelated UIA session presenting the very
+    // same token must be rejected - tokens are single use regardless of
+    // which session presents them.
+    let other_device_id = format!("token-reuse-{}", Uuid::new_v4().to_hyphenated());
+    let request = matrix_sdk::api::r0::device::delete_device::Request::new((&other_device_id).into());
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("uia error expected");
+    let uiaa_response = err.uiaa_response().expect("uia response expected");
+
+    let mut request = matrix_sdk::api::r0::device::delete_device::Request::new((&other_device_id).into());
+    let mut auth_parameters = BTreeMap::new();
+    auth_parameters.insert("token".to_owned(), json!(token));
+    request.auth = Some(matrix_sdk::api::r0::uiaa::AuthData::DirectRequest {
+        kind: "m.login.token",
+        session: uiaa_response.session.as_deref(),
+        auth_parameters,
+    });
+
+    let err = client
+        .send(request, None)
+        .await
+        .err()
+        .expect("a token already consumed by a previous session must not complete a new one");
+
+    assert_matches!(
+        err,
+        matrix_sdk::Error::Http(matrix_sdk::HttpError::UiaaError(_))
+    );
+
+    Ok(())
+}
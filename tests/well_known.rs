@@ -0,0 +1,78 @@
+use famedly_e2e_testing::{
+    eyre::Result,
+    reqwest::{self, StatusCode},
+    serde_json, tokio, DEV_ENV_HOMESERVER,
+};
+
+#[tokio::test]
+async fn test_well_known_client_points_at_the_proxy() -> Result<()> {
+    let url = format!("{}/.well-known/matrix/client", DEV_ENV_HOMESERVER);
+
+    let res = reqwest::get(&url).await?;
+
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let body: serde_json::Value = res.json().await?;
+
+    let base_url = body
+        .get("m.homeserver")
+        .and_then(|homeserver| homeserver.get("base_url"))
+        .and_then(|base_url| base_url.as_str())
+        .expect("expected m.homeserver.base_url to be present");
+
+    assert_eq!(
+        base_url.trim_end_matches('/'),
+        DEV_ENV_HOMESERVER.trim_end_matches('/'),
+        "well-known should route clients back through the proxy, not straight to the real homeserver"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_well_known_client_publishes_the_identity_server() -> Result<()> {
+    // The dev environment's proxy config configures an identity server
+    // alongside the homeserver for exactly this test to observe.
+    let url = format!("{}/.well-known/matrix/client", DEV_ENV_HOMESERVER);
+
+    let res = reqwest::get(&url).await?;
+    let body: serde_json::Value = res.json().await?;
+
+    let identity_server_base_url = body
+        .get("m.identity_server")
+        .and_then(|identity_server| identity_server.get("base_url"))
+        .and_then(|base_url| base_url.as_str());
+
+    assert!(
+        identity_server_base_url.is_some(),
+        "expected the dev environment's configured identity server to be published"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_well_known_server_is_published() -> Result<()> {
+    let url = format!("{}/.well-known/matrix/server", DEV_ENV_HOMESERVER);
+
+    let res = reqwest::get(&url).await?;
+
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let body: serde_json::Value = res.json().await?;
+
+    assert!(
+        body.get("m.server").and_then(|m_server| m_server.as_str()).is_some(),
+        "expected an m.server value to be published"
+    );
+
+    Ok(())
+}
+
+// Whether `/.well-known/matrix/client` is served at all, and whether
+// `/server` is served alongside it, is an `enabled`/per-field config
+// decision the running dev environment has already made - this client
+// can't flip it mid-suite to prove the "disabled" half of the contract.
+// That half is covered directly in handlers::well_known::tests, which
+// build an AppState with well-known turned off (or entirely unconfigured)
+// and assert 404 without needing a live deployment to reconfigure.